@@ -0,0 +1,98 @@
+//! Opaque handle registry for values shared across the FFI boundary, modeled on PkmnLib's
+//! `handle` module.
+//!
+//! A raw pointer handed to a C caller lets them double-free it, or keep using it after another
+//! wrapper function has already consumed and dropped it; a naive read is unsound if the pointer
+//! crosses threads too. A [`Registry`] avoids all of this: callers only ever hold an opaque
+//! integer handle, the registry is the sole owner of the underlying value, and every access goes
+//! through a lock that also checks the accessing thread matches the one the handle was created
+//! on.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+/// An opaque handle to a value registered with a [`Registry`].
+pub type Handle = u64;
+
+/// Why a [`Registry`] lookup failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    /// No value is registered under this handle; it may never have existed, or may already have
+    /// been removed.
+    NotFound,
+    /// The handle was created on a different thread to the one now trying to use it.
+    WrongThread,
+}
+
+struct Entry<T> {
+    value: T,
+    owner: ThreadId,
+}
+
+/// A thread-affine registry of values of type `T`, addressed by opaque [`Handle`]s.
+pub struct Registry<T> {
+    next_handle: AtomicU64,
+    entries: Mutex<HashMap<Handle, Entry<T>>>,
+}
+
+impl<T> Registry<T> {
+    pub fn new() -> Self {
+        Registry {
+            next_handle: AtomicU64::new(1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers `value`, bound to the calling thread, and returns a handle to it.
+    pub fn insert(&self, value: T) -> Handle {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        let entry = Entry {
+            value,
+            owner: thread::current().id(),
+        };
+        self.entries.lock().unwrap().insert(handle, entry);
+        handle
+    }
+
+    /// Runs `f` with a shared reference to the value behind `handle`.
+    pub fn with<R>(&self, handle: Handle, f: impl FnOnce(&T) -> R) -> Result<R, HandleError> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&handle).ok_or(HandleError::NotFound)?;
+        if entry.owner != thread::current().id() {
+            return Err(HandleError::WrongThread);
+        }
+        Ok(f(&entry.value))
+    }
+
+    /// Runs `f` with a mutable reference to the value behind `handle`.
+    pub fn with_mut<R>(
+        &self,
+        handle: Handle,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, HandleError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(&handle).ok_or(HandleError::NotFound)?;
+        if entry.owner != thread::current().id() {
+            return Err(HandleError::WrongThread);
+        }
+        Ok(f(&mut entry.value))
+    }
+
+    /// Removes the value behind `handle`, if any.
+    ///
+    /// Removing a handle that's already gone is not an error: it simply does nothing, which is
+    /// what makes repeated `destroy` calls on the C side safe.
+    pub fn remove(&self, handle: Handle) -> Result<(), HandleError> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&handle) {
+            None => Ok(()),
+            Some(entry) if entry.owner != thread::current().id() => Err(HandleError::WrongThread),
+            Some(_) => {
+                entries.remove(&handle);
+                Ok(())
+            }
+        }
+    }
+}
@@ -5,11 +5,23 @@
 //! library but not tied to using rust.
 //!
 //! The bindings are in an early state at present; they simply return integers representing the
-//! results of operations.
+//! results of operations. Where a function can fail for more than one reason, the returned
+//! integer corresponds to a [`ThudStatus`] discriminant, and [`thud_last_error_message`] can be
+//! used to recover a human-readable description of the failure.
+mod handle;
+
+use handle::Handle;
+use handle::HandleError;
+use handle::Registry;
+use libc::c_char;
 use libc::c_int;
 use libc::c_uint;
+use libc::c_void;
+use std::cell::RefCell;
+use std::ffi::CString;
 use std::ptr;
 use std::slice;
+use std::sync::OnceLock;
 use thud::Coord;
 use thud::Direction;
 use thud::EndState;
@@ -17,6 +29,15 @@ use thud::Piece;
 use thud::Player;
 use thud::Thud;
 
+/// Registry of every live [`ThudState`], addressed by the handles [`thud_new`] hands out.
+///
+/// `Registry::new` allocates a `HashMap`, which isn't a `const fn`, so the registry can't be a
+/// plain `static`; it's lazily built on first use instead.
+fn registry() -> &'static Registry<ThudState> {
+    static REGISTRY: OnceLock<Registry<ThudState>> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::new)
+}
+
 fn piece_to_int(piece: Piece) -> c_uint {
     match piece {
         Piece::Empty => 0,
@@ -26,10 +47,260 @@ fn piece_to_int(piece: Piece) -> c_uint {
     }
 }
 
+/// Status codes returned by the fallible FFI wrappers in this module.
+///
+/// `NullPointer`, `IllegalMove` and `InvalidDirection` carry the same values `thud_move`,
+/// `thud_attack` and `thud_troll_cap` have always returned (`-1`, `-2` and `-3` respectively);
+/// this just gives them a name and lets [`thud_last_error_message`] fill in the detail a bare
+/// discriminant can't carry (which player's turn it was, which coordinate was out of bounds, and
+/// so on). Every other variant is new in this discriminant range and was never returned before.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThudStatus {
+    /// The operation completed successfully.
+    Ok = 0,
+    /// A pointer argument was null where a valid one was required.
+    NullPointer = -1,
+    /// The requested move, attack or troll-cap was not legal.
+    IllegalMove = -2,
+    /// A direction argument did not correspond to one of [`Direction`]'s variants.
+    InvalidDirection = -3,
+    /// A coordinate argument did not refer to a valid board position.
+    InvalidCoord = -4,
+    /// The game has already ended.
+    GameOver = -5,
+    /// No Thud is registered under the given handle; it may never have existed, or may already
+    /// have been destroyed.
+    HandleNotFound = -6,
+    /// The handle was created on a different thread to the one now trying to use it.
+    WrongThread = -7,
+    /// A caller-provided buffer was too small to hold the result.
+    BufferTooSmall = -8,
+}
+
+fn handle_error_status(err: HandleError) -> ThudStatus {
+    match err {
+        HandleError::NotFound => ThudStatus::HandleNotFound,
+        HandleError::WrongThread => ThudStatus::WrongThread,
+    }
+}
+
+fn handle_error_message(err: HandleError) -> &'static str {
+    match err {
+        HandleError::NotFound => "no Thud is registered under this handle",
+        HandleError::WrongThread => "this handle was created on a different thread",
+    }
+}
+
+thread_local! {
+    /// The message describing the most recent error set on this thread, if any.
+    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+fn set_last_error(message: impl Into<String>) {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message.into()));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = None);
+}
+
+/// Copies a description of the last error recorded on the calling thread into `buf`.
+///
+/// Returns the number of bytes the message occupies (excluding the terminating nul). If `buf` is
+/// null or `len` is too small to hold the message and its terminator, nothing is written and the
+/// message length is returned anyway, so callers can retry with a buffer of at least that length
+/// plus one for the terminator. Returns `0`,
+/// writing nothing, if no error has been recorded on this thread.
+#[no_mangle]
+pub unsafe extern "C" fn thud_last_error_message(buf: *mut c_char, len: c_uint) -> c_int {
+    LAST_ERROR.with(|slot| {
+        let message = match &*slot.borrow() {
+            Some(message) => message.clone(),
+            None => return 0,
+        };
+        let required = message.len() as c_int;
+        if buf.is_null() || (len as usize) < message.len() + 1 {
+            return required;
+        }
+        let c_message = match CString::new(message) {
+            Ok(c_message) => c_message,
+            Err(_) => return required,
+        };
+        ptr::copy_nonoverlapping(c_message.as_ptr(), buf, c_message.as_bytes_with_nul().len());
+        required
+    })
+}
+
+/// Event category bitflags, as passed to [`thud_register_listener`] and carried on
+/// [`ThudEvent::kind`].
+pub const THUD_EVENT_PIECE_MOVED: c_uint = 1 << 0;
+/// See [`THUD_EVENT_PIECE_MOVED`].
+pub const THUD_EVENT_PIECE_CAPTURED: c_uint = 1 << 1;
+/// See [`THUD_EVENT_PIECE_MOVED`].
+pub const THUD_EVENT_TURN_CHANGED: c_uint = 1 << 2;
+/// See [`THUD_EVENT_PIECE_MOVED`].
+pub const THUD_EVENT_GAME_ENDED: c_uint = 1 << 3;
+
+/// An event fired on a [`Thud`] after a successful [`thud_move`], [`thud_attack`] or
+/// [`thud_troll_cap`] call. Passed by reference to listeners registered with
+/// [`thud_register_listener`]; it does not outlive the callback invocation.
+///
+/// `src` and `dest` are null where not applicable to `kind` (for instance, a troll-cap may
+/// capture more than one destination, so its events carry only `src`). `player` is `0` for
+/// Dwarf, `1` for Troll, or `-1` where not applicable.
+#[repr(C)]
+pub struct ThudEvent {
+    pub kind: c_uint,
+    pub src: *const Coord,
+    pub dest: *const Coord,
+    pub piece: c_uint,
+    pub player: c_int,
+}
+
+type ThudListenerCallback = extern "C" fn(event: *const ThudEvent, user: *mut c_void);
+
+#[derive(Clone, Copy)]
+struct ThudListener {
+    mask: c_uint,
+    callback: ThudListenerCallback,
+    user: *mut c_void,
+}
+
+// SAFETY: `user` is an opaque token the C caller handed us in `thud_register_listener` and gets
+// back unchanged in its own callback; we never dereference it ourselves. The only place a
+// `ThudListener` is read back out is `fire_event`, and every path that reaches it goes through
+// `Registry::with`/`with_mut`'s thread-affinity check first, so `user` is always read back on
+// the same thread that registered it even though it now lives behind a `Sync` `Mutex`.
+unsafe impl Send for ThudListener {}
+
+/// A [`Thud`] together with the event listeners registered on it. `Thud` itself has no room for
+/// C callbacks, so this wrapper is what [`thud_new`] actually hands out.
+pub struct ThudState {
+    thud: Thud,
+    listeners: Vec<ThudListener>,
+}
+
+/// Invokes every listener in `listeners` whose mask matches `event`.
+///
+/// `listeners` is a snapshot cloned while the registry's lock was held, and this must only be
+/// called *after* that lock for the owning [`ThudState`] has been released: a callback is free
+/// to call back into any handle-bearing FFI function
+/// (including a different handle on the same thread), and [`Registry`]'s lock is not reentrant,
+/// so firing straight out of a `with`/`with_mut` closure would deadlock the first time a caller
+/// did that.
+fn fire_event(listeners: &[ThudListener], event: &ThudEvent) {
+    for listener in listeners {
+        if listener.mask & event.kind != 0 {
+            (listener.callback)(event, listener.user);
+        }
+    }
+}
+
+fn player_to_int(player: Player) -> c_int {
+    match player {
+        Player::Dwarf => 0,
+        Player::Troll => 1,
+    }
+}
+
+/// Why `thud_move`, `thud_attack` or `thud_troll_cap` failed to apply a move.
+enum MoveFailure {
+    /// The game had already ended; the underlying `thud` method was never called.
+    GameOver,
+    /// The underlying `thud` method rejected the move.
+    Illegal(String),
+}
+
+/// Builds the [`THUD_EVENT_TURN_CHANGED`] or [`THUD_EVENT_GAME_ENDED`] event reflecting the
+/// state of `state.thud` after a move has been applied, if any. This only constructs the event;
+/// see [`fire_event`] for why firing it is the caller's job, done after the registry lock has
+/// been released.
+fn turn_or_game_end_event(state: &ThudState) -> Option<ThudEvent> {
+    match state.thud.turn() {
+        Some(player) => Some(ThudEvent {
+            kind: THUD_EVENT_TURN_CHANGED,
+            src: ptr::null(),
+            dest: ptr::null(),
+            piece: piece_to_int(Piece::Empty),
+            player: player_to_int(player),
+        }),
+        None => state.thud.winner().map(|end| {
+            let player = match end {
+                EndState::Won(player) => player_to_int(player),
+                EndState::Draw => -1,
+            };
+            ThudEvent {
+                kind: THUD_EVENT_GAME_ENDED,
+                src: ptr::null(),
+                dest: ptr::null(),
+                piece: piece_to_int(Piece::Empty),
+                player,
+            }
+        }),
+    }
+}
+
+/// Registers a callback on the Thud behind `handle` that fires whenever an event matching `kind`
+/// (a bitwise OR of `THUD_EVENT_*` flags) occurs. `user` is passed back to the callback unchanged
+/// on every invocation, letting callers thread through their own context.
+///
+/// Returns a [`ThudStatus`] discriminant; `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if
+/// `handle` doesn't resolve to a Thud registered on the calling thread.
+#[no_mangle]
+pub extern "C" fn thud_register_listener(
+    handle: Handle,
+    kind: c_uint,
+    cb: ThudListenerCallback,
+    user: *mut c_void,
+) -> c_int {
+    let result = registry().with_mut(handle, |state| {
+        state.listeners.push(ThudListener {
+            mask: kind,
+            callback: cb,
+            user,
+        });
+    });
+    match result {
+        Ok(()) => {
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
+    }
+}
+
+/// Removes every listener registered on the Thud behind `handle` via [`thud_register_listener`].
+///
+/// Returns a [`ThudStatus`] discriminant; `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if
+/// `handle` doesn't resolve to a Thud registered on the calling thread.
+#[no_mangle]
+pub extern "C" fn thud_clear_listeners(handle: Handle) -> c_int {
+    match registry().with_mut(handle, |state| state.listeners.clear()) {
+        Ok(()) => {
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
+    }
+}
+
 /// Wrapper for [`Thud::new()`](struct.Thud.html#method.new)
+///
+/// Registers the new Thud in [`registry`] and returns a handle to it, rather than a raw pointer:
+/// see [`handle`] for why.
 #[no_mangle]
-pub extern "C" fn thud_new() -> *mut Thud {
-    Box::into_raw(Box::new(Thud::new()))
+pub extern "C" fn thud_new() -> Handle {
+    registry().insert(ThudState {
+        thud: Thud::new(),
+        listeners: Vec::new(),
+    })
 }
 
 /// Wrapper for [`Coord::zero_based()`](struct.Coord.html#method.zero_based).
@@ -43,11 +314,22 @@ pub extern "C" fn coord_new(x: c_uint, y: c_uint) -> *mut Coord {
     }
 }
 
-/// Release a Thud from memory.
+/// Removes the Thud behind `handle` from [`registry`], releasing it from memory.
+///
+/// Destroying a handle that's already been destroyed (or never existed) is a safe no-op. Returns
+/// a [`ThudStatus`] discriminant; `-7` (`WrongThread`) if `handle` was created on a different
+/// thread to this one.
 #[no_mangle]
-pub unsafe extern "C" fn thud_destroy(thud_raw: *mut Thud) {
-    if !thud_raw.is_null() {
-        drop(Box::from_raw(thud_raw));
+pub extern "C" fn thud_destroy(handle: Handle) -> c_int {
+    match registry().remove(handle) {
+        Ok(()) => {
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
@@ -61,47 +343,131 @@ pub unsafe extern "C" fn coord_destroy(coord_raw: *mut Coord) {
 
 /// Wrapper for [`Thud::move_piece`](struct.Thud.html#method.move_piece).
 ///
-/// Returns:
+/// Returns a [`ThudStatus`] discriminant:
 ///
-/// - `0` if the move was made successfully
-/// - `-1` if any pointers passed were null
-/// - `-2` if the move was illegal
+/// - `0` (`Ok`) if the move was made successfully
+/// - `-1` (`NullPointer`) if any pointers passed were null
+/// - `-2` (`IllegalMove`) if the move was illegal
+/// - `-5` (`GameOver`) if the game has already ended
+/// - `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if `handle` doesn't resolve to a Thud
+///   registered on the calling thread
+///
+/// Call [`thud_last_error_message`] to retrieve the reason for a non-`Ok` result.
 #[no_mangle]
 pub unsafe extern "C" fn thud_move(
-    thud_raw: *mut Thud,
+    handle: Handle,
     src_raw: *mut Coord,
     dest_raw: *mut Coord,
 ) -> c_int {
-    if thud_raw.is_null() || src_raw.is_null() || dest_raw.is_null() {
-        return -1;
+    if src_raw.is_null() || dest_raw.is_null() {
+        set_last_error("a null pointer was passed where a valid pointer was required");
+        return ThudStatus::NullPointer as c_int;
     }
-    let mut thud = Box::from_raw(thud_raw);
-    match thud.move_piece(*src_raw, *dest_raw) {
-        Ok(_) => 0,
-        _ => -2,
+    let result = registry().with_mut(handle, |state| {
+        let (piece, player) = match state.thud.turn() {
+            Some(Player::Dwarf) => (piece_to_int(Piece::Dwarf), 0),
+            Some(Player::Troll) => (piece_to_int(Piece::Troll), 1),
+            None => return Err(MoveFailure::GameOver),
+        };
+        match state.thud.move_piece(*src_raw, *dest_raw) {
+            Ok(_) => {
+                let mut events = vec![ThudEvent {
+                    kind: THUD_EVENT_PIECE_MOVED,
+                    src: src_raw,
+                    dest: dest_raw,
+                    piece,
+                    player,
+                }];
+                events.extend(turn_or_game_end_event(state));
+                Ok((state.listeners.clone(), events))
+            }
+            Err(e) => Err(MoveFailure::Illegal(format!("{:?}", e))),
+        }
+    });
+    match result {
+        Ok(Ok((listeners, events))) => {
+            for event in &events {
+                fire_event(&listeners, event);
+            }
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Ok(Err(MoveFailure::GameOver)) => {
+            set_last_error("the game has already ended");
+            ThudStatus::GameOver as c_int
+        }
+        Ok(Err(MoveFailure::Illegal(message))) => {
+            set_last_error(message);
+            ThudStatus::IllegalMove as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
 /// Wrapper for [`Thud::attack()`](struct.Thud.html#method.attack).
 ///
-/// Returns:
+/// Returns a [`ThudStatus`] discriminant:
 ///
-/// - `0` if the move was made successfully
-/// - `-1` if any pointers passed were null
-/// - `-2` if the move was illegal
+/// - `0` (`Ok`) if the move was made successfully
+/// - `-1` (`NullPointer`) if any pointers passed were null
+/// - `-2` (`IllegalMove`) if the move was illegal
+/// - `-5` (`GameOver`) if the game has already ended
+/// - `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if `handle` doesn't resolve to a Thud
+///   registered on the calling thread
+///
+/// Call [`thud_last_error_message`] to retrieve the reason for a non-`Ok` result.
 #[no_mangle]
 pub unsafe extern "C" fn thud_attack(
-    thud_raw: *mut Thud,
+    handle: Handle,
     src_raw: *mut Coord,
     dest_raw: *mut Coord,
 ) -> c_int {
-    if thud_raw.is_null() || src_raw.is_null() || dest_raw.is_null() {
-        return -1;
+    if src_raw.is_null() || dest_raw.is_null() {
+        set_last_error("a null pointer was passed where a valid pointer was required");
+        return ThudStatus::NullPointer as c_int;
     }
-    let mut thud = Box::from_raw(thud_raw);
-    match thud.attack(*src_raw, *dest_raw) {
-        Ok(_) => 0,
-        _ => -2,
+    let result = registry().with_mut(handle, |state| {
+        if state.thud.turn().is_none() {
+            return Err(MoveFailure::GameOver);
+        }
+        match state.thud.attack(*src_raw, *dest_raw) {
+            Ok(_) => {
+                let mut events = vec![ThudEvent {
+                    kind: THUD_EVENT_PIECE_CAPTURED,
+                    src: src_raw,
+                    dest: dest_raw,
+                    piece: piece_to_int(Piece::Dwarf),
+                    player: player_to_int(Player::Dwarf),
+                }];
+                events.extend(turn_or_game_end_event(state));
+                Ok((state.listeners.clone(), events))
+            }
+            Err(e) => Err(MoveFailure::Illegal(format!("{:?}", e))),
+        }
+    });
+    match result {
+        Ok(Ok((listeners, events))) => {
+            for event in &events {
+                fire_event(&listeners, event);
+            }
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Ok(Err(MoveFailure::GameOver)) => {
+            set_last_error("the game has already ended");
+            ThudStatus::GameOver as c_int
+        }
+        Ok(Err(MoveFailure::Illegal(message))) => {
+            set_last_error(message);
+            ThudStatus::IllegalMove as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
@@ -109,20 +475,21 @@ pub unsafe extern "C" fn thud_attack(
 ///
 /// Returns:
 ///
-/// - `-1` if `thud_raw` is a null pointer
 /// - `0` for a Dwarf turn
 /// - `1` for a Troll turn
 /// - `2` for an ended game
+/// - `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if `handle` doesn't resolve to a Thud
+///   registered on the calling thread
 #[no_mangle]
-pub unsafe extern "C" fn thud_get_turn(thud_raw: *mut Thud) -> c_int {
-    if thud_raw.is_null() {
-        return -1;
-    }
-    let thud = Box::from_raw(thud_raw);
-    match thud.turn() {
-        Some(Player::Dwarf) => 0,
-        Some(Player::Troll) => 1,
-        _ => 2,
+pub extern "C" fn thud_get_turn(handle: Handle) -> c_int {
+    match registry().with(handle, |state| state.thud.turn()) {
+        Ok(Some(Player::Dwarf)) => 0,
+        Ok(Some(Player::Troll)) => 1,
+        Ok(None) => 2,
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
@@ -130,66 +497,88 @@ pub unsafe extern "C" fn thud_get_turn(thud_raw: *mut Thud) -> c_int {
 ///
 /// Returns:
 ///
-/// - `-1` if `thud_raw` is a null pointer
 /// - `0` for a Dwarf victory
 /// - `1` for a Troll victory
 /// - `2` for a draw
 /// - `3` if the game hasn't ended yet
+/// - `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if `handle` doesn't resolve to a Thud
+///   registered on the calling thread
 #[no_mangle]
-pub unsafe extern "C" fn thud_get_winner(thud_raw: *mut Thud) -> c_int {
-    if thud_raw.is_null() {
-        return -1;
-    }
-    let mut thud = Box::from_raw(thud_raw);
-    match thud.winner() {
-        Some(EndState::Won(Player::Dwarf)) => 0,
-        Some(EndState::Won(Player::Troll)) => 1,
-        Some(EndState::Draw) => 2,
-        _ => 3,
+pub extern "C" fn thud_get_winner(handle: Handle) -> c_int {
+    match registry().with_mut(handle, |state| state.thud.winner()) {
+        Ok(Some(EndState::Won(Player::Dwarf))) => 0,
+        Ok(Some(EndState::Won(Player::Troll))) => 1,
+        Ok(Some(EndState::Draw)) => 2,
+        Ok(None) => 3,
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
 /// Wrapper for [`Thud::score()`](struct.Thud.html#method.score)
 ///
-/// Returns a 2-element array of `c_int` holding:
-///
-/// 1. The Dwarf score
-/// 2. The Troll score
+/// Writes the Dwarf score into `out_dwarf` and the Troll score into `out_troll`.
 ///
-/// Returns a null pointer if `thud_raw` is a null pointer.
+/// Returns a [`ThudStatus`] discriminant: `-1` (`NullPointer`) if either pointer is null, or
+/// `-6`/`-7` if `handle` doesn't resolve to a Thud registered on the calling thread. Like
+/// [`thud_get_board`], nothing is allocated on the crate side; the caller owns both out
+/// parameters.
 #[no_mangle]
-pub unsafe extern "C" fn thud_get_score(thud_raw: *mut Thud) -> *mut c_int {
-    if thud_raw.is_null() {
-        return ptr::null_mut();
+pub unsafe extern "C" fn thud_get_score(
+    handle: Handle,
+    out_dwarf: *mut c_int,
+    out_troll: *mut c_int,
+) -> c_int {
+    if out_dwarf.is_null() || out_troll.is_null() {
+        set_last_error("a null pointer was passed where a valid pointer was required");
+        return ThudStatus::NullPointer as c_int;
+    }
+    match registry().with(handle, |state| state.thud.score()) {
+        Ok((dwarf, troll)) => {
+            *out_dwarf = dwarf as c_int;
+            *out_troll = troll as c_int;
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
-    let thud = Box::from_raw(thud_raw);
-    let (dwarf, troll) = thud.score();
-    ([dwarf as c_int, troll as c_int]).as_mut_ptr()
 }
 
 /// Wrapper for [`Thud::troll_cap()`](struct.Thud.html#method.troll_cap).
 ///
 /// Takes:
 ///
-/// - Pointer to a `Thud`
+/// - Handle to a `Thud`
 /// - Pointer to a `Coord`
 /// - 8-element array of `c_uint`; each of these should be between `0` and `8` inclusive.
 ///   They map to directions, with `Direction::Right` being 0, incrementing clockwise.
 ///
-/// Returns:
+/// Returns a [`ThudStatus`] discriminant:
 ///
-/// - `-3` if any elements of targets_raw were invalid directions.
-/// - `-2` if the move was illegal
-/// - `-1` if any arguments were null pointers
-/// - `0` if the move finished successfully
+/// - `0` (`Ok`) if the move finished successfully
+/// - `-1` (`NullPointer`) if any arguments were null pointers
+/// - `-2` (`IllegalMove`) if the move was illegal
+/// - `-3` (`InvalidDirection`) if an index of targets_raw whose element is `1` did not map to a
+///   direction
+/// - `-5` (`GameOver`) if the game has already ended
+/// - `-6` (`HandleNotFound`) or `-7` (`WrongThread`) if `handle` doesn't resolve to a Thud
+///   registered on the calling thread
+///
+/// Call [`thud_last_error_message`] to retrieve the reason for a non-`Ok` result.
 #[no_mangle]
 pub unsafe extern "C" fn thud_troll_cap(
-    thud_raw: *mut Thud,
+    handle: Handle,
     src_raw: *mut Coord,
     targets_raw: *mut c_uint,
 ) -> c_int {
-    if thud_raw.is_null() || src_raw.is_null() || targets_raw.is_null() {
-        return -1;
+    if src_raw.is_null() || targets_raw.is_null() {
+        set_last_error("a null pointer was passed where a valid pointer was required");
+        return ThudStatus::NullPointer as c_int;
     }
     let targets = slice::from_raw_parts(targets_raw, 8);
     let mut attack_dirs = Vec::with_capacity(8);
@@ -197,40 +586,124 @@ pub unsafe extern "C" fn thud_troll_cap(
         if targets[i] == 1 {
             attack_dirs.push(match Direction::from_num(i) {
                 Ok(dir) => dir,
-                _ => return -3,
+                Err(e) => {
+                    set_last_error(format!("direction {} was invalid: {:?}", i, e));
+                    return ThudStatus::InvalidDirection as c_int;
+                }
             });
         }
     }
 
-    let mut thud = Box::from_raw(thud_raw);
-    match thud.troll_cap(*src_raw, attack_dirs) {
-        Ok(_) => 0,
-        _ => -2,
+    let result = registry().with_mut(handle, |state| {
+        if state.thud.turn().is_none() {
+            return Err(MoveFailure::GameOver);
+        }
+        match state.thud.troll_cap(*src_raw, attack_dirs) {
+            Ok(_) => {
+                let mut events = vec![ThudEvent {
+                    kind: THUD_EVENT_PIECE_CAPTURED,
+                    src: src_raw,
+                    dest: ptr::null(),
+                    piece: piece_to_int(Piece::Troll),
+                    player: player_to_int(Player::Troll),
+                }];
+                events.extend(turn_or_game_end_event(state));
+                Ok((state.listeners.clone(), events))
+            }
+            Err(e) => Err(MoveFailure::Illegal(format!("{:?}", e))),
+        }
+    });
+    match result {
+        Ok(Ok((listeners, events))) => {
+            for event in &events {
+                fire_event(&listeners, event);
+            }
+            clear_last_error();
+            ThudStatus::Ok as c_int
+        }
+        Ok(Err(MoveFailure::GameOver)) => {
+            set_last_error("the game has already ended");
+            ThudStatus::GameOver as c_int
+        }
+        Ok(Err(MoveFailure::Illegal(message))) => {
+            set_last_error(message);
+            ThudStatus::IllegalMove as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
     }
 }
 
+/// Number of cells in a Thud board (15 * 15), and the minimum length `out` must have in
+/// [`thud_get_board`].
+pub const THUD_BOARD_CELLS: c_uint = 225;
+
 /// Wrapper for [`Thud::board()`](struct.Thud.html#method.board).
 ///
-/// Returns a 15 by 15 nested array of `c_uint` with piece represented as:
+/// Writes the 15 by 15 grid row-major into the caller-provided `out`, which must point to at
+/// least `len` elements, with piece represented as:
 ///
 /// - `0` for an empty space
 /// - `1` for a Dwarf piece
 /// - `2` for a Troll piece
-/// - `3` for the Thundstone
+/// - `3` for the Thudstone
+///
+/// Returns the number of cells written ([`THUD_BOARD_CELLS`]) on success. Returns a [`ThudStatus`]
+/// discriminant on failure: `-1` (`NullPointer`) if `out` is null, `-8` (`BufferTooSmall`) if
+/// `len` is smaller than [`THUD_BOARD_CELLS`], or `-6`/`-7` if `handle` doesn't resolve to a Thud
+/// registered on the calling thread. Unlike the previous pointer-returning version, nothing is
+/// allocated on the crate side: the caller owns `out` for as long as it needs it.
 #[no_mangle]
-pub unsafe extern "C" fn thud_get_board(thud_raw: *mut Thud) -> *mut *mut c_uint {
-    if thud_raw.is_null() {
-        return ptr::null_mut();
+pub unsafe extern "C" fn thud_get_board(handle: Handle, out: *mut c_uint, len: c_uint) -> c_int {
+    if out.is_null() {
+        set_last_error("a null pointer was passed where a valid pointer was required");
+        return ThudStatus::NullPointer as c_int;
     }
-    let board = Box::from_raw(thud_raw).board().full_raw();
-    let mut result = Vec::with_capacity(15);
+    if len < THUD_BOARD_CELLS {
+        set_last_error(format!(
+            "buffer of length {} is too small for {} board cells",
+            len, THUD_BOARD_CELLS
+        ));
+        return ThudStatus::BufferTooSmall as c_int;
+    }
+    let board = match registry().with(handle, |state| state.thud.board().full_raw()) {
+        Ok(board) => board,
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            return handle_error_status(e) as c_int;
+        }
+    };
     for x in 0..15 {
-        result.push(
-            (0..15)
-                .map(|y| piece_to_int(board[x][y]) as c_uint)
-                .collect::<Vec<c_uint>>()
-                .as_mut_ptr(),
-        );
-    }
-    result.as_mut_ptr()
+        for y in 0..15 {
+            *out.add(x * 15 + y) = piece_to_int(board[x][y]);
+        }
+    }
+    clear_last_error();
+    THUD_BOARD_CELLS as c_int
+}
+
+/// Wrapper for [`Thud::board()`](struct.Thud.html#method.board) for a single cell, following the
+/// same cell encoding as [`thud_get_board`].
+///
+/// Returns the encoded piece at `(x, y)`, or a negative [`ThudStatus`] discriminant: `-4`
+/// (`InvalidCoord`) if `x` or `y` is outside the board's 15x15 range, or `-6`/`-7` if `handle`
+/// doesn't resolve to a Thud registered on the calling thread.
+#[no_mangle]
+pub extern "C" fn thud_board_cell(handle: Handle, x: c_uint, y: c_uint) -> c_int {
+    if x >= 15 || y >= 15 {
+        set_last_error(format!("coordinate ({}, {}) is outside the 15x15 board", x, y));
+        return ThudStatus::InvalidCoord as c_int;
+    }
+    match registry().with(handle, |state| state.thud.board().full_raw()) {
+        Ok(board) => {
+            clear_last_error();
+            piece_to_int(board[x as usize][y as usize]) as c_int
+        }
+        Err(e) => {
+            set_last_error(handle_error_message(e));
+            handle_error_status(e) as c_int
+        }
+    }
 }